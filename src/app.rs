@@ -0,0 +1,662 @@
+use crate::{config, log};
+use std::{cell::{Cell, OnceCell, RefCell}, ffi::c_void, time::{Duration, Instant}};
+use dispatch2::{DispatchObject, DispatchQueue, DispatchSource, _dispatch_source_type_signal};
+use objc2::{class, define_class, msg_send, sel, rc::Retained,
+    runtime::{AnyObject, ProtocolObject, Sel}, DefinedClass, MainThreadOnly};
+use objc2_app_kit::{NSApplication, NSApplicationActivationPolicy, NSApplicationDelegate,
+    NSControlStateValueOff, NSControlStateValueOn, NSMenu, NSMenuDelegate, NSMenuItem, NSResponder,
+    NSRunningApplication, NSScreen, NSStatusBar, NSStatusItem, NSVariableStatusItemLength};
+use objc2_foundation::{ns_string, MainThreadMarker, NSNotification, NSObject, NSObjectProtocol,
+    NSRect, NSString, NSUserDefaults};
+extern "C" {
+    fn kill(pid: i32, sig: i32) -> i32; fn fork() -> i32; fn setsid() -> i32;
+    fn dup2(old: i32, new: i32) -> i32;
+    fn signal(sig: i32, handler: usize) -> usize;
+    fn sysctlbyname(name: *const std::ffi::c_char, old: *mut c_void, old_len: *mut usize,
+        new: *mut c_void, new_len: usize) -> i32;
+}
+const SIGHUP: i32 = 1;
+const SIGTERM: i32 = 15;
+const SIGUSR1: i32 = 30;
+const WORDS: &str = "--no-menu --verbose toggle hide status restart reset export import doctor \
+    daemon-pid completions";
+const POSITION_KEYS: [&str; 2] =
+    ["NSStatusItem Preferred Position Item-0", "NSStatusItem Preferred Position Pusher-0"];
+const EXPORT_NAMES: [&str; 2] = ["divider_position", "pusher_position"];
+const COMPETITORS: [(&str, &str); 5] = [
+    ("com.surteesstudios.Bartender", "Bartender"), ("com.jordanbaird.Ice", "Ice"),
+    ("com.dwarvesv.minimalbar", "Hidden Bar"), ("com.mortennn.Dozer", "Dozer"),
+    ("net.matthewpalmer.Vanilla", "Vanilla"),
+];
+#[derive(Debug)] struct DaemonIvars {
+    status_item: OnceCell<Retained<NSStatusItem>>, pusher_item: OnceCell<Retained<NSStatusItem>>,
+    toggle_item: OnceCell<Retained<NSMenuItem>>, grab_item: OnceCell<Retained<NSMenuItem>>,
+    lock_item: OnceCell<Retained<NSMenuItem>>,
+    config: RefCell<config::Config>, hidden: Cell<bool>, grabbing: Cell<bool>, locked: Cell<bool>,
+    last_scroll: Cell<Option<Instant>>, animation: Cell<Option<(Instant, f64)>>,
+    hide_length: Cell<f64>,
+}
+
+define_class!(
+    #[unsafe(super(NSResponder, NSObject))]
+    #[thread_kind = MainThreadOnly]
+    #[ivars = DaemonIvars]
+    #[derive(Debug)]
+    struct Delegate;
+    impl Delegate {
+        #[unsafe(method(toggleVisibility:))]
+        fn toggle_visibility(&self, _: Option<&AnyObject>) { self.click_toggle(); }
+        #[unsafe(method(toggleGrab:))]
+        fn toggle_grab(&self, _: Option<&AnyObject>) { self.set_grab(!self.ivars().grabbing.get()); }
+        #[unsafe(method(ungrab:))]
+        fn ungrab(&self, _: Option<&AnyObject>) { self.set_grab(false); }
+        #[unsafe(method(animationStep:))]
+        fn animation_step(&self, _: Option<&AnyObject>) { self.step_animation(); }
+        #[unsafe(method(autoHide:))]
+        fn auto_hide(&self, _: Option<&AnyObject>) { self.set_hidden(true); }
+        #[unsafe(method(toggleLock:))]
+        fn toggle_lock(&self, _: Option<&AnyObject>) {
+            let locked = !self.ivars().locked.get();
+            if locked { self.set_hidden(true); }
+            self.ivars().locked.set(locked);
+            self.write_state();
+            if let Some(l) = self.ivars().lock_item.get() {
+                l.setState(if locked { NSControlStateValueOn } else { NSControlStateValueOff });
+            }
+        }
+        #[unsafe(method(scrollWheel:))]
+        fn scroll_wheel(&self, event: &AnyObject) {
+            let dy: f64 = unsafe { msg_send![event, scrollingDeltaY] };
+            let quiet = self.ivars().last_scroll.get()
+                .is_none_or(|t| t.elapsed() >= Duration::from_millis(300));
+            if quiet && dy.abs() >= 1.0 && !self.ivars().locked.get() {
+                self.ivars().last_scroll.set(Some(Instant::now()));
+                self.set_hidden(dy < 0.0);
+            }
+        }
+    }
+    unsafe impl NSObjectProtocol for Delegate {}
+    unsafe impl NSApplicationDelegate for Delegate {
+        #[unsafe(method(applicationDidFinishLaunching:))]
+        fn did_finish_launching(&self, _: &NSNotification) {
+            let mtm = self.mtm();
+            self.ivars().config.replace(config::load());
+            self.ivars().hide_length.set(hide_length(&self.ivars().config.borrow(), mtm));
+            let glyph = NSString::from_str(&self.ivars().config.borrow().expanded_glyph);
+            let bar = NSStatusBar::systemStatusBar();
+            let mut item = divider_item(&bar, &glyph, mtm);
+            if item.button(mtm).is_none() {
+                log::error("divider has no button, retrying");
+                bar.removeStatusItem(&item);
+                std::thread::sleep(std::time::Duration::from_millis(500));
+                item = divider_item(&bar, &glyph, mtm);
+                if item.button(mtm).is_none() {
+                    log::error("could not create the divider status item");
+                    std::process::exit(1);
+                }
+            }
+            if record_visibility(&item, mtm) && misplaced(&item, mtm) {
+                log::info("divider misplaced, resetting its saved position");
+                bar.removeStatusItem(&item);
+                NSUserDefaults::standardUserDefaults()
+                    .removeObjectForKey(&NSString::from_str(POSITION_KEYS[0]));
+                item = divider_item(&bar, &glyph, mtm);
+                record_visibility(&item, mtm);
+            }
+            let pusher = bar.statusItemWithLength(NSVariableStatusItemLength);
+            pusher.setAutosaveName(Some(ns_string!("Pusher-0")));
+            if let Some(b) = pusher.button(mtm) { b.setTitle(ns_string!("\u{200B}")); }
+            let menu = NSMenu::new(mtm);
+            let toggle = self.menu_item(ns_string!("Hide Icons"), sel!(toggleVisibility:));
+            menu.addItem(&toggle);
+            let grab = self.menu_item(ns_string!("Widen Divider"), sel!(toggleGrab:));
+            menu.addItem(&grab);
+            let lock = self.menu_item(ns_string!("Lock Hidden"), sel!(toggleLock:));
+            menu.addItem(&lock);
+            let quit = unsafe { NSMenuItem::initWithTitle_action_keyEquivalent(
+                NSMenuItem::alloc(mtm), ns_string!("Quit"), Some(sel!(terminate:)), ns_string!("")) };
+            menu.addItem(&quit);
+            menu.setDelegate(Some(ProtocolObject::from_ref(self as &Delegate)));
+            if NSUserDefaults::standardUserDefaults().boolForKey(ns_string!("NoMenu")) {
+                if let Some(b) = item.button(mtm) { unsafe {
+                    b.setTarget(Some(self.as_ref()));
+                    b.setAction(Some(sel!(toggleVisibility:)));
+                } }
+            } else { item.setMenu(Some(&menu)); }
+            if let Some(w) = item.button(mtm).and_then(|b| b.window()) {
+                unsafe { w.setNextResponder(Some(self)) };
+            }
+            self.ivars().status_item.set(item).unwrap();
+            self.ivars().pusher_item.set(pusher).unwrap();
+            self.ivars().toggle_item.set(toggle).unwrap();
+            self.ivars().grab_item.set(grab).unwrap();
+            self.ivars().lock_item.set(lock).unwrap();
+            self.on_signal(SIGUSR1, toggle_on_signal);
+            self.on_signal(SIGHUP, reload_on_signal);
+            if saved_hidden() { self.set_hidden(true); }
+            self.write_state();
+            if self.ivars().config.borrow().warn_conflicts {
+                for name in conflicts() { log::warn(&conflict_warning(name)); }
+            }
+            let _ = std::fs::write(pid_path(), std::process::id().to_string());
+            log::info(&format!("started (pid {})", std::process::id()));
+        }
+        #[unsafe(method(applicationDidChangeScreenParameters:))]
+        fn did_change_screen_parameters(&self, _: &NSNotification) {
+            log::debug("screen parameters changed");
+            self.refit();
+            if let Some(item) = self.ivars().status_item.get() { record_visibility(item, self.mtm()); }
+        }
+        #[unsafe(method(applicationWillTerminate:))]
+        fn will_terminate(&self, _: &NSNotification) {
+            log::info("quit");
+            let _ = std::fs::remove_file(pid_path());
+            let _ = std::fs::remove_file(offscreen_path());
+        }
+    }
+    unsafe impl NSMenuDelegate for Delegate {
+        #[unsafe(method(menuWillOpen:))]
+        fn menu_will_open(&self, menu: &NSMenu) {
+            let mtm = self.mtm();
+            let is_left: bool = unsafe {
+                let e: *const AnyObject =
+                    msg_send![&*NSApplication::sharedApplication(mtm), currentEvent];
+                e.is_null() || { let b: isize = msg_send![e, buttonNumber]; b == 0 }
+            };
+            if is_left {
+                menu.cancelTrackingWithoutAnimation();
+                self.click_toggle();
+            }
+        }
+    }
+);
+fn divider_item(bar: &NSStatusBar, title: &NSString, mtm: MainThreadMarker)
+    -> Retained<NSStatusItem>
+{
+    let item = bar.statusItemWithLength(NSVariableStatusItemLength);
+    item.setAutosaveName(Some(ns_string!("Item-0")));
+    if let Some(b) = item.button(mtm) { b.setTitle(title); }
+    item
+}
+fn shown_frame(item: &NSStatusItem, mtm: MainThreadMarker) -> Option<NSRect> {
+    let window = item.button(mtm)?.window()?;
+    Some(window.frame()).filter(|f| window.isVisible() && f.size.width > 0.0)
+}
+fn misplaced(item: &NSStatusItem, mtm: MainThreadMarker) -> bool {
+    shown_frame(item, mtm).is_some_and(|f| NSScreen::screens(mtm).iter().all(|s| {
+        let s = s.frame();
+        f.origin.x + f.size.width <= s.origin.x || f.origin.x >= s.origin.x + s.size.width
+            || f.origin.y + f.size.height <= s.origin.y || f.origin.y >= s.origin.y + s.size.height
+    }))
+}
+fn record_visibility(item: &NSStatusItem, mtm: MainThreadMarker) -> bool {
+    let shown = shown_frame(item, mtm).is_some();
+    if shown { let _ = std::fs::remove_file(offscreen_path()); } else {
+        log::warn("divider not visible; free up menu bar space");
+        let _ = std::fs::write(offscreen_path(), "");
+    }
+    shown
+}
+fn conflicts() -> Vec<&'static str> {
+    COMPETITORS.iter().filter(|(id, _)| {
+        NSRunningApplication::runningApplicationsWithBundleIdentifier(&NSString::from_str(id)).count() > 0
+    }).map(|&(_, name)| name).collect()
+}
+fn conflict_warning(name: &str) -> String {
+    format!("{name} is also running; it may fight nanobar over the menu bar layout")
+}
+fn hide_length(config: &config::Config, mtm: MainThreadMarker) -> f64 {
+    if config.hide_length > 0.0 { return config.hide_length; }
+    NSScreen::mainScreen(mtm).map_or(10000.0, |s| s.frame().size.width + 500.0)
+}
+impl Delegate {
+    fn new(mtm: MainThreadMarker) -> Retained<Self> {
+        let this = Self::alloc(mtm).set_ivars(DaemonIvars {
+            status_item: OnceCell::new(), pusher_item: OnceCell::new(), toggle_item: OnceCell::new(),
+            grab_item: OnceCell::new(), lock_item: OnceCell::new(), config: RefCell::default(),
+            hidden: Cell::new(false), grabbing: Cell::new(false), locked: Cell::new(false),
+            last_scroll: Cell::new(None), animation: Cell::new(None), hide_length: Cell::new(0.0),
+        });
+        unsafe { msg_send![super(this), init] }
+    }
+    fn menu_item(&self, title: &NSString, action: Sel) -> Retained<NSMenuItem> {
+        let item = unsafe { NSMenuItem::initWithTitle_action_keyEquivalent(
+            NSMenuItem::alloc(self.mtm()), title, Some(action), ns_string!("")) };
+        unsafe { item.setTarget(Some(self.as_ref())) };
+        item
+    }
+    fn on_signal(&self, sig: i32, handler: extern "C" fn(*mut c_void)) {
+        unsafe { signal(sig, 1) };
+        let source = unsafe { DispatchSource::new(&raw const _dispatch_source_type_signal as *mut _,
+            sig as usize, 0, Some(DispatchQueue::main())) };
+        unsafe { source.set_context(self as *const Self as *mut c_void) };
+        source.set_event_handler_f(handler);
+        source.resume();
+        std::mem::forget(source);
+    }
+    fn toggle(&self) {
+        if !self.ivars().locked.get() { self.set_hidden(!self.ivars().hidden.get()); }
+    }
+    fn click_toggle(&self) {
+        let before = self.ivars().hidden.get();
+        self.toggle();
+        let hidden = self.ivars().hidden.get();
+        if hidden != before && self.ivars().config.borrow().notify { notify(hidden); }
+    }
+    fn set_hidden(&self, hidden: bool) {
+        let changed = self.ivars().hidden.replace(hidden) != hidden;
+        let from = self.ivars().pusher_item.get().unwrap().length().max(0.0);
+        self.ivars().animation.set(Some((Instant::now(), from)));
+        self.step_animation();
+        self.refresh_title();
+        if let Some(t) = self.ivars().toggle_item.get() {
+            t.setTitle(if hidden { ns_string!("Show Icons") } else { ns_string!("Hide Icons") });
+        }
+        let auto_hide = self.ivars().config.borrow().auto_hide;
+        self.schedule(sel!(autoHide:), (!hidden && auto_hide > 0.0).then_some(auto_hide));
+        if changed {
+            let state = if hidden { "hidden" } else { "shown" };
+            self.write_state();
+            log::info(state);
+            run_hook(state, &self.ivars().config.borrow());
+        }
+    }
+    fn refit(&self) {
+        self.ivars().hide_length.set(hide_length(&self.ivars().config.borrow(), self.mtm()));
+        if self.ivars().hidden.get() { self.set_hidden(true); }
+        self.write_state();
+    }
+    fn write_state(&self) {
+        let state = if self.ivars().hidden.get() { "hidden" } else { "shown" };
+        let lock = if self.ivars().locked.get() { "locked\n" } else { "" };
+        let config = self.ivars().config.borrow();
+        save_state(&format!("{state}\n{lock}hide_length {}\nexpanded_glyph {}\n\
+            collapsed_glyph {}\n", self.ivars().hide_length.get(), config.expanded_glyph,
+            config.collapsed_glyph));
+    }
+    fn step_animation(&self) {
+        let Some((start, from)) = self.ivars().animation.get() else { return };
+        let pusher = self.ivars().pusher_item.get().unwrap();
+        let hidden = self.ivars().hidden.get();
+        let config = self.ivars().config.borrow();
+        let to = if hidden { self.ivars().hide_length.get() } else { 0.0 };
+        let t = if config.animation_ms > 0.0 {
+            (start.elapsed().as_secs_f64() * 1000.0 / config.animation_ms).min(1.0)
+        } else { 1.0 };
+        if t < 1.0 {
+            pusher.setLength(from + (to - from) * (1.0 - (1.0 - t) * (1.0 - t)));
+            return self.schedule(sel!(animationStep:), Some(1.0 / 60.0));
+        }
+        pusher.setLength(if hidden { to } else { NSVariableStatusItemLength });
+        self.ivars().animation.set(None);
+    }
+    fn refresh_title(&self) {
+        let item = self.ivars().status_item.get();
+        let Some(button) = item.and_then(|i| i.button(self.mtm())) else { return };
+        if self.ivars().grabbing.get() { return button.setTitle(ns_string!("\u{2194}")); }
+        let config = self.ivars().config.borrow();
+        button.setTitle(&NSString::from_str(if self.ivars().hidden.get() { &config.collapsed_glyph }
+            else { &config.expanded_glyph }));
+    }
+    fn set_grab(&self, grab: bool) {
+        let item = self.ivars().status_item.get().unwrap();
+        item.setLength(if grab { 40.0 } else { NSVariableStatusItemLength });
+        self.ivars().grabbing.set(grab);
+        self.refresh_title();
+        if let Some(g) = self.ivars().grab_item.get() {
+            g.setTitle(if grab { ns_string!("Restore Divider") } else { ns_string!("Widen Divider") });
+        }
+        self.schedule(sel!(ungrab:), grab.then_some(30.0));
+    }
+    fn schedule(&self, action: Sel, delay: Option<f64>) {
+        let nil = std::ptr::null::<AnyObject>();
+        unsafe {
+            let _: () = msg_send![class!(NSObject), cancelPreviousPerformRequestsWithTarget: self,
+                selector: action, object: nil];
+            if let Some(delay) = delay {
+                let _: () = msg_send![self, performSelector: action, withObject: nil, afterDelay: delay];
+            }
+        }
+    }
+}
+
+extern "C" fn toggle_on_signal(delegate: *mut c_void) {
+    log::debug("SIGUSR1: toggle");
+    unsafe { &*(delegate as *const Delegate) }.toggle();
+}
+extern "C" fn reload_on_signal(delegate: *mut c_void) {
+    let delegate = unsafe { &*(delegate as *const Delegate) };
+    delegate.ivars().config.replace(config::load());
+    delegate.refresh_title();
+    delegate.refit();
+    log::info("SIGHUP: config reloaded");
+}
+fn run_hook(state: &str, config: &config::Config) {
+    let (key, cmd) = if state == "hidden" { ("on_hide", &config.on_hide) }
+        else { ("on_show", &config.on_show) };
+    if cmd.is_empty() { return; }
+    match std::process::Command::new("/bin/sh").args(["-c", cmd]).env("NANOBAR_STATE", state)
+        .stdin(std::process::Stdio::null()).spawn()
+    {
+        Ok(mut child) => {
+            log::debug(&format!("{key}: spawned pid {}", child.id()));
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => log::error(&format!("{key} failed: {e}")),
+    }
+}
+fn notify(hidden: bool) {
+    let text = if hidden { "Menu bar items hidden" } else { "Menu bar items shown" };
+    let script = format!("display notification \"{text}\" with title \"nanobar\"");
+    match std::process::Command::new("osascript").args(["-e", &script])
+        .stdout(std::process::Stdio::null()).stderr(std::process::Stdio::null()).spawn()
+    {
+        Ok(mut child) => { std::thread::spawn(move || child.wait()); }
+        Err(e) => log::debug(&format!("notification failed: {e}")),
+    }
+}
+fn runtime_override() -> Option<std::path::PathBuf> {
+    std::env::var_os("NANOBAR_RUNTIME_DIR").filter(|d| !d.is_empty()).map(Into::into)
+}
+fn runtime_dir() -> std::path::PathBuf { runtime_override().unwrap_or_else(std::env::temp_dir) }
+fn pid_path() -> std::path::PathBuf { runtime_dir().join("nanobar.pid") }
+fn state_path() -> std::path::PathBuf {
+    if let Some(dir) = runtime_override() { return dir.join("nanobar.state"); }
+    std::env::var_os("HOME").map_or_else(runtime_dir,
+        |home| std::path::PathBuf::from(home).join("Library/Application Support/nanobar")).join("state")
+}
+fn save_state(state: &str) {
+    let path = state_path();
+    if let Some(dir) = path.parent() { let _ = std::fs::create_dir_all(dir); }
+    if let Err(e) = std::fs::write(&path, state) { log::error(&format!("{}: {e}", path.display())); }
+}
+fn offscreen_path() -> std::path::PathBuf { runtime_dir().join("nanobar.offscreen") }
+fn saved_state() -> String { std::fs::read_to_string(state_path()).unwrap_or_default() }
+fn saved_field(key: &str) -> Option<String> {
+    saved_state().lines().find_map(|l| l.strip_prefix(key)?.strip_prefix(' ').map(String::from))
+}
+fn saved_hidden() -> bool { saved_state().lines().next() == Some("hidden") }
+fn saved_locked() -> bool { saved_state().lines().any(|l| l == "locked") }
+fn running_pid() -> Option<i32> {
+    std::fs::read_to_string(pid_path()).ok().and_then(|s| s.trim().parse::<i32>().ok())
+        .filter(|&pid| unsafe { kill(pid, 0) } == 0)
+}
+fn remove_stale_pid() -> bool {
+    pid_path().exists() && running_pid().is_none() && std::fs::remove_file(pid_path()).is_ok()
+}
+fn uptime(secs: u64) -> String {
+    let (d, h, m) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+    if d > 0 { format!("{d}d{h}h") } else if h > 0 { format!("{h}h{m}m") }
+    else if m > 0 { format!("{m}m") } else { format!("{secs}s") }
+}
+fn stop_daemon(pid: i32) {
+    unsafe { kill(pid, SIGTERM) };
+    for _ in 0..60 {
+        if running_pid().is_none() { remove_stale_pid(); return; }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    eprintln!("nanobar: pid {pid} did not exit"); std::process::exit(1);
+}
+fn write_position(key: &str, pos: f64) {
+    let written = std::process::Command::new("defaults")
+        .args(["write", "nanobar", key, "-float", &pos.to_string()])
+        .status().is_ok_and(|s| s.success());
+    if !written { eprintln!("nanobar: could not write {key}"); std::process::exit(1); }
+}
+fn position_line(line: &str) -> Option<(&'static str, Option<f64>)> {
+    let (key, value) = line.split_once('=')?;
+    let i = EXPORT_NAMES.iter().position(|&n| n == key.trim())?;
+    let pos = value.split('#').next()?.trim().parse::<f64>().ok().filter(|p| p.is_finite());
+    Some((POSITION_KEYS[i], pos))
+}
+fn valid_position(x: f64) -> bool {
+    let screen = MainThreadMarker::new().and_then(NSScreen::mainScreen);
+    x.is_finite() && x > 0.0 && screen.is_some_and(|s| x < s.frame().size.width)
+}
+fn start_fresh() -> ! {
+    let started = std::env::current_exe().and_then(|exe| std::process::Command::new(exe).status());
+    std::process::exit(started.map_or(1, |s| s.code().unwrap_or(1)))
+}
+fn supported_os() -> bool {
+    let (mut buf, mut len) = ([0u8; 32], 32);
+    let ok = unsafe { sysctlbyname(c"kern.osrelease".as_ptr(), buf.as_mut_ptr().cast(), &mut len,
+        std::ptr::null_mut(), 0) } == 0;
+    let release = std::str::from_utf8(&buf[..len]).unwrap_or("").trim_end_matches('\0');
+    ok && release.split('.').next().and_then(|m| m.parse::<u32>().ok()).is_some_and(|m| m >= 17)
+}
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+fn doctor() -> bool {
+    let mut ok = true;
+    let mut check = |pass: bool, what: &str, hint: &str| {
+        if pass { println!("\u{2713} {what}"); } else { ok = false; println!("\u{2717} {what}: {hint}"); }
+    };
+    check(supported_os(), "macOS 10.13 or later", "nanobar can't run on this system");
+    if running_pid().is_some() {
+        check(true, "daemon running", "");
+        check(!offscreen_path().exists(), "divider visible",
+            "the menu bar is full; quit some menu bar apps, then `nanobar restart`");
+    } else {
+        check(false, "daemon running", "start it with `nanobar`");
+    }
+    if let Some(path) = config::path().filter(|p| p.exists()) {
+        let errors = std::fs::read_to_string(&path).map_or_else(|e| vec![e.to_string()],
+            |src| config::parse(&src).1);
+        check(errors.is_empty(), "config file parses",
+            &format!("fix {}: {}", path.display(), errors.join(", ")));
+    }
+    let agent = std::env::var_os("HOME")
+        .map(|home| std::path::PathBuf::from(home).join("Library/LaunchAgents/nanobar.plist"));
+    if let Some(agent) = agent.filter(|p| p.exists()) {
+        let valid = std::process::Command::new("plutil").args(["-lint", "-s"]).arg(&agent)
+            .status().is_ok_and(|s| s.success());
+        check(valid, "LaunchAgent plist is valid", &format!("rewrite {}", agent.display()));
+        let exe = std::env::current_exe().ok().map(|e| xml_escape(&e.to_string_lossy()));
+        let current = std::fs::read_to_string(&agent).ok().zip(exe)
+            .is_some_and(|(plist, exe)| plist.contains(&format!("<string>{exe}</string>")));
+        check(current, "LaunchAgent starts this binary", "redo the Auto-start step in the README");
+        let loaded = std::process::Command::new("launchctl").args(["list", "nanobar"])
+            .stdout(std::process::Stdio::null()).stderr(std::process::Stdio::null())
+            .status().is_ok_and(|s| s.success());
+        check(loaded, "LaunchAgent loaded", &format!("run `launchctl load -w {}`", agent.display()));
+    }
+    for name in conflicts() {
+        check(false, "no other menu bar manager", &format!("quit {name} to avoid jitter"));
+    }
+    ok
+}
+
+pub fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("daemon-pid") => match running_pid() {
+            Some(pid) => return println!("{pid}"),
+            None => std::process::exit(1),
+        },
+        Some("status") => match running_pid() {
+            Some(pid) => {
+                let hidden = saved_hidden();
+                let up = std::fs::metadata(pid_path()).and_then(|m| m.modified()).ok()
+                    .and_then(|t| t.elapsed().ok()).map_or(0, |d| d.as_secs());
+                let state = if hidden { "hidden" } else { "shown" };
+                let lock = if saved_locked() { ", locked" } else { "" };
+                let (config, errors) = config::read();
+                for e in errors { eprintln!("warning: config: skipped {e}"); }
+                if config.warn_conflicts {
+                    for name in conflicts() { eprintln!("warning: {}", conflict_warning(name)); }
+                }
+                let length = saved_field("hide_length").map_or("unknown".into(), |l| l + "pt");
+                println!("running (pid {pid}, up {}), {state}{lock}, hide length {length}", uptime(up));
+                let glyph = |key| saved_field(key).unwrap_or_else(|| "?".into());
+                let (expanded, collapsed) = (glyph("expanded_glyph"), glyph("collapsed_glyph"));
+                println!("glyphs: {expanded} shown, {collapsed} hidden");
+                if offscreen_path().exists() {
+                    println!("divider not visible: the menu bar is full, free up space and restart");
+                }
+                std::process::exit(if hidden { 2 } else { 0 });
+            }
+            None => {
+                println!("{}", if pid_path().exists() { "stale pid file (process dead)" }
+                    else { "not running" });
+                std::process::exit(1);
+            }
+        },
+        Some("reset") => {
+            let running = running_pid().inspect(|&pid| stop_daemon(pid)).is_some();
+            if remove_stale_pid() { println!("deleted stale {}", pid_path().display()); }
+            for key in POSITION_KEYS {
+                let deleted = std::process::Command::new("defaults").args(["delete", "nanobar", key])
+                    .stderr(std::process::Stdio::null()).status().is_ok_and(|s| s.success());
+                if deleted { println!("deleted {key}"); }
+            }
+            if !running { return; }
+        }
+        Some("hide") => {
+            let at = match &args[1..] {
+                [flag, x] if flag == "--at" => x.parse::<f64>().ok().filter(|&x| valid_position(x)),
+                _ => None,
+            };
+            let Some(at) = at else {
+                eprintln!("nanobar: hide needs --at <position>, points from the screen's right edge");
+                std::process::exit(1);
+            };
+            if let Some(pid) = running_pid() { stop_daemon(pid); }
+            for (key, pos) in POSITION_KEYS.into_iter().zip([at, at + 1.0]) { write_position(key, pos); }
+            save_state("hidden\n");
+            println!("divider at {at}, icons hidden");
+            start_fresh();
+        }
+        Some("export") => {
+            for (key, name) in POSITION_KEYS.into_iter().zip(EXPORT_NAMES) {
+                let out = std::process::Command::new("defaults").args(["read", "nanobar", key])
+                    .stderr(std::process::Stdio::null()).output().ok();
+                let pos = out.and_then(|o| String::from_utf8(o.stdout).ok()?.trim().parse::<f64>().ok());
+                if let Some(pos) = pos { println!("{name} = {pos}"); }
+            }
+            if let Some(src) = config::path().and_then(|p| std::fs::read_to_string(p).ok()) {
+                print!("{src}");
+            }
+            return;
+        }
+        Some("import") => {
+            let Some(src) = args.get(1).and_then(|f| std::fs::read_to_string(f).ok()) else {
+                eprintln!("nanobar: import needs a readable file from `nanobar export`");
+                std::process::exit(1);
+            };
+            let (mut positions, mut config) = (Vec::new(), String::new());
+            for line in src.lines() {
+                match position_line(line) {
+                    None => { config.push_str(line); config.push('\n'); }
+                    Some((key, Some(pos))) if valid_position(pos) => positions.push((key, pos)),
+                    Some(_) => { eprintln!("nanobar: invalid position: {line}"); std::process::exit(1); }
+                }
+            }
+            let errors = config::parse(&config).1;
+            if !errors.is_empty() {
+                eprintln!("nanobar: invalid configuration in {}: {}", args[1], errors.join(", "));
+                std::process::exit(1);
+            }
+            let running = running_pid().inspect(|&pid| stop_daemon(pid)).is_some();
+            for (key, pos) in positions { write_position(key, pos); println!("{key} = {pos}"); }
+            if !config.trim().is_empty() {
+                let Some(path) = config::path() else { std::process::exit(1) };
+                let written = path.parent().is_some_and(|dir| std::fs::create_dir_all(dir).is_ok())
+                    && std::fs::write(&path, config).is_ok();
+                if !written {
+                    eprintln!("nanobar: could not write {}", path.display()); std::process::exit(1);
+                }
+                println!("wrote {}", path.display());
+            }
+            if running { start_fresh(); }
+            return;
+        }
+        Some("doctor") => std::process::exit(i32::from(!doctor())),
+        Some("restart") => {
+            if let Some(pid) = running_pid() { stop_daemon(pid); println!("stopped pid {pid}"); }
+            println!("starting, icons {}", if saved_hidden() { "hidden" } else { "shown" });
+        }
+        Some("completions") => return match args.get(1).map(String::as_str) {
+            Some("bash") => println!("complete -W \"{WORDS}\" nanobar"),
+            Some("zsh") => println!("#compdef nanobar\ncompadd -- {WORDS}"),
+            Some("fish") => println!("complete -c nanobar -f -a \"{WORDS}\""),
+            _ => { eprintln!("nanobar: completions needs bash, zsh or fish"); std::process::exit(1); }
+        },
+        Some("toggle") => match running_pid() {
+            Some(_) if saved_locked() => { println!("locked"); std::process::exit(3); }
+            Some(pid) => {
+                let before = saved_hidden();
+                unsafe { kill(pid, SIGUSR1) };
+                for _ in 0..20 {
+                    if saved_hidden() != before {
+                        return println!("{}", if saved_hidden() { "hidden" } else { "shown" });
+                    }
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                }
+                eprintln!("nanobar: no response from daemon (pid {pid})"); std::process::exit(1);
+            }
+            None => { eprintln!("nanobar: not running"); std::process::exit(1); }
+        },
+        _ if args.iter().all(|a| a == "--no-menu" || a == "--verbose") => {}
+        _ => return println!("nanobar {} - minimal macOS menu bar manager\n\
+            Usage: nanobar [--no-menu] [--verbose] | toggle | status | restart | reset\n\
+            \x20      nanobar hide --at <position> | export | import <file> | doctor | daemon-pid\n\
+            \x20      nanobar completions bash|zsh|fish\n\
+            status exits 0 if shown, 1 if not running, 2 if hidden\n\
+            toggle exits 1 if not running, 3 if locked", env!("CARGO_PKG_VERSION")),
+    }
+    if !supported_os() {
+        eprintln!("nanobar: requires macOS 10.13 or later"); std::process::exit(1);
+    }
+    if running_pid().is_some() { eprintln!("nanobar: already running"); std::process::exit(1); }
+    remove_stale_pid();
+    let pid = unsafe { fork() };
+    if pid != 0 { std::process::exit(if pid > 0 { 0 } else { 1 }); }
+    unsafe { setsid(); }
+    if let Ok(null) = std::fs::OpenOptions::new().read(true).write(true).open("/dev/null") {
+        use std::os::fd::AsRawFd;
+        for fd in 0..3 { unsafe { dup2(null.as_raw_fd(), fd) }; }
+    }
+    log::set_verbose(args.iter().any(|a| a == "--verbose"));
+    let mtm = MainThreadMarker::new().unwrap();
+    if args.iter().any(|a| a == "--no-menu") {
+        NSUserDefaults::standardUserDefaults().setBool_forKey(true, ns_string!("NoMenu"));
+    }
+    let app = NSApplication::sharedApplication(mtm);
+    app.setActivationPolicy(NSApplicationActivationPolicy::Accessory);
+    let delegate = Delegate::new(mtm);
+    app.setDelegate(Some(ProtocolObject::from_ref(&*delegate)));
+    app.run();
+    let _ = std::fs::remove_file(pid_path());
+    log::error("run loop exited unexpectedly");
+    std::process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uptime_units() {
+        for (secs, want) in [(0, "0s"), (59, "59s"), (60, "1m"), (3600, "1h0m"), (86400, "1d0h")] {
+            assert_eq!(uptime(secs), want);
+        }
+    }
+
+    #[test]
+    fn xml_escapes_path() {
+        assert_eq!(xml_escape("/Apps/A&B <x>/nanobar"), "/Apps/A&amp;B &lt;x&gt;/nanobar");
+        assert_eq!(xml_escape("/usr/local/bin/nanobar"), "/usr/local/bin/nanobar");
+    }
+
+    #[test]
+    fn import_lines() {
+        assert_eq!(position_line("divider_position = 812.5"), Some((POSITION_KEYS[0], Some(812.5))));
+        assert_eq!(position_line("pusher_position=3 # moved"), Some((POSITION_KEYS[1], Some(3.0))));
+        assert_eq!(position_line("divider_position = abc"), Some((POSITION_KEYS[0], None)));
+        assert_eq!(position_line("divider_position = inf"), Some((POSITION_KEYS[0], None)));
+        assert_eq!(position_line("expanded_glyph = \"●\""), None);
+        assert_eq!(position_line("divider_positions = 1"), None);
+        assert_eq!(position_line("# divider_position = 1"), None);
+    }
+}
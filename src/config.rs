@@ -0,0 +1,163 @@
+use std::path::PathBuf;
+use crate::log;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub expanded_glyph: String,
+    pub collapsed_glyph: String,
+    pub auto_hide: f64,
+    pub hide_length: f64,
+    pub warn_conflicts: bool,
+    pub animation_ms: f64,
+    pub notify: bool,
+    pub on_hide: String,
+    pub on_show: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            expanded_glyph: "\u{203a}".into(), collapsed_glyph: "\u{2039}".into(), auto_hide: 0.0,
+            hide_length: 0.0, warn_conflicts: true, animation_ms: 150.0, notify: false,
+            on_hide: String::new(), on_show: String::new(),
+        }
+    }
+}
+
+pub fn path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config/nanobar/config.toml"))
+}
+
+pub fn load() -> Config {
+    let (config, errors) = read();
+    for e in errors { log::warn(&format!("config: skipped {e}")); }
+    config
+}
+
+pub fn read() -> (Config, Vec<String>) {
+    let src = path().and_then(|p| std::fs::read_to_string(p).ok()).unwrap_or_default();
+    let (mut config, errors) = parse(&src);
+    let env = |var| std::env::var(var).ok().filter(|v: &String| !v.is_empty());
+    if let Some(g) = env("NANOBAR_GLYPH_SHOWN") { config.expanded_glyph = g; }
+    if let Some(g) = env("NANOBAR_GLYPH_HIDDEN") { config.collapsed_glyph = g; }
+    if let Some(c) = env("NANOBAR_ON_HIDE") { config.on_hide = c; }
+    if let Some(c) = env("NANOBAR_ON_SHOW") { config.on_show = c; }
+    (config, errors)
+}
+
+pub fn parse(src: &str) -> (Config, Vec<String>) {
+    let (mut config, mut errors) = (Config::default(), Vec::new());
+    for (n, line) in src.lines().enumerate().map(|(n, l)| (n + 1, l.trim())) {
+        if line.is_empty() || line.starts_with('#') { continue; }
+        if set(&mut config, line).is_none() { errors.push(format!("line {n}: {line}")); }
+    }
+    (config, errors)
+}
+
+fn set(config: &mut Config, line: &str) -> Option<()> {
+    let (key, value) = line.split_once('=')?;
+    let value = value.trim();
+    match key.trim() {
+        "expanded_glyph" => config.expanded_glyph = string(value)?,
+        "collapsed_glyph" => config.collapsed_glyph = string(value)?,
+        "auto_hide" => config.auto_hide = number(value)?,
+        "hide_length" => config.hide_length = number(value)?,
+        "animation_ms" => config.animation_ms = number(value)?,
+        "notify" => config.notify = boolean(value)?,
+        "warn_conflicts" => config.warn_conflicts = boolean(value)?,
+        "on_hide" => config.on_hide = string(value)?,
+        "on_show" => config.on_show = string(value)?,
+        _ => {}
+    }
+    Some(())
+}
+
+fn string(value: &str) -> Option<String> {
+    let (s, rest) = if let Some(literal) = value.strip_prefix('\'') {
+        let (s, rest) = literal.split_once('\'')?;
+        (s.to_string(), rest)
+    } else {
+        let mut chars = value.strip_prefix('"')?.char_indices();
+        let mut s = String::new();
+        loop {
+            match chars.next()? {
+                (i, '"') => break (s, &value[i + 2..]),
+                (_, '\\') => s.push(match chars.next()?.1 {
+                    'n' => '\n', 't' => '\t', '"' => '"', '\\' => '\\',
+                    u @ ('u' | 'U') => {
+                        let len = if u == 'u' { 4 } else { 8 };
+                        let hex: String =
+                            (0..len).map(|_| chars.next().map(|(_, c)| c)).collect::<Option<_>>()?;
+                        char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                    }
+                    _ => return None,
+                }),
+                (_, c) => s.push(c),
+            }
+        }
+    };
+    let rest = rest.trim();
+    (!s.is_empty() && (rest.is_empty() || rest.starts_with('#'))).then_some(s)
+}
+
+fn number(value: &str) -> Option<f64> {
+    let n: f64 = value.split('#').next()?.trim().parse().ok()?;
+    (n.is_finite() && n >= 0.0).then_some(n)
+}
+
+fn boolean(value: &str) -> Option<bool> {
+    value.split('#').next()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments() {
+        let (config, errors) = parse("# glyphs\nexpanded_glyph = \"●\"  # shown\n\nauto_hide = 5 # s\n");
+        assert_eq!((config.expanded_glyph.as_str(), config.auto_hide), ("●", 5.0));
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn bad_line_keeps_the_rest() {
+        let (config, errors) = parse("auto_hide = 5s\nexpanded_glyph = \"●\"\nnotify = yes\nbogus\n");
+        assert_eq!(config.expanded_glyph, "●");
+        assert_eq!(config.auto_hide, Config::default().auto_hide);
+        assert_eq!(errors, ["line 1: auto_hide = 5s", "line 3: notify = yes", "line 4: bogus"]);
+    }
+
+    #[test]
+    fn single_quotes() {
+        let (config, errors) = parse("expanded_glyph = '›'\ncollapsed_glyph = '\\u2039' # raw\n");
+        assert_eq!(config.expanded_glyph, "›");
+        assert_eq!(config.collapsed_glyph, "\\u2039");
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn escapes() {
+        let (config, errors) = parse(r#"expanded_glyph = "\u203a"
+collapsed_glyph = "\"\\\U00002039""#);
+        assert_eq!(config.expanded_glyph, "\u{203a}");
+        assert_eq!(config.collapsed_glyph, "\"\\\u{2039}");
+        assert!(errors.is_empty());
+        let (_, errors) = parse("expanded_glyph = \"\\q\"\ncollapsed_glyph = \"open\n");
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn hooks() {
+        let (config, errors) = parse("on_hide = 'say \"hidden\"'\non_show = \"open -g x\"\n");
+        assert_eq!((config.on_hide.as_str(), config.on_show.as_str()), ("say \"hidden\"", "open -g x"));
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn unknown_keys_are_ignored() {
+        let (config, errors) = parse("future_key = whatever\n");
+        assert_eq!(config, Config::default());
+        assert!(errors.is_empty());
+    }
+}
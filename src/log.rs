@@ -0,0 +1,52 @@
+use std::{fs, io::Write, path::PathBuf, sync::atomic::{AtomicBool, Ordering},
+    time::{SystemTime, UNIX_EPOCH}};
+
+const MAX_LEN: u64 = 1 << 20;
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_verbose(verbose: bool) { VERBOSE.store(verbose, Ordering::Relaxed); }
+pub fn path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Logs/nanobar.log"))
+}
+pub fn error(msg: &str) { write("ERROR", msg); }
+pub fn warn(msg: &str) { write("WARN", msg); }
+pub fn info(msg: &str) { write("INFO", msg); }
+pub fn debug(msg: &str) { if VERBOSE.load(Ordering::Relaxed) { write("DEBUG", msg); } }
+
+fn write(level: &str, msg: &str) {
+    let Some(path) = path() else { return };
+    if fs::metadata(&path).is_ok_and(|m| m.len() >= MAX_LEN) {
+        let _ = fs::rename(&path, path.with_extension("log.1"));
+    }
+    if let Ok(mut f) = fs::OpenOptions::new().create(true).append(true).open(&path) {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64);
+        let _ = writeln!(f, "{} {level} {msg}", format_time(secs));
+    }
+}
+
+fn format_time(secs: i64) -> String {
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z", rem / 3600, rem / 60 % 60, rem % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_dates() {
+        assert_eq!(format_time(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_time(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_time(1_704_067_199), "2023-12-31T23:59:59Z");
+        assert_eq!(format_time(1_704_067_200), "2024-01-01T00:00:00Z");
+    }
+}